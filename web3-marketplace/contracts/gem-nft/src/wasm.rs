@@ -0,0 +1,1541 @@
+// WASM exports
+//
+// Every export takes the current contract state and a JSON argument object as
+// (ptr, len) pairs. Hosts write inputs into buffers obtained from `alloc` and
+// release them with `dealloc` once the call returns.
+//
+// Exports return a pointer to a length-prefixed result: a little-endian u32
+// byte count followed by that many bytes of JSON. The host frees it with
+// `dealloc(ptr, 4 + count)`. The JSON is an envelope:
+//
+//   mutating calls: {"ok": true, "state": {...}, "result": ..., "events": [...]}
+//   queries:        {"ok": true, "result": ...}
+//   failures:       {"ok": false, "error": {"code": ..., "message": ...}}
+//
+// Addresses are nchain wallet addresses: 16 lowercase hex characters such as
+// "48175356aa0b5e87". Anything else is rejected as invalid arguments. State
+// from before addresses were validated must go through `migrate_legacy_state`
+// once; every other export rejects it.
+//
+// On failure the host keeps its previous state. Exports never panic on bad
+// input: malformed state or arguments, invalid UTF-8 and null pointers all
+// come back as failure envelopes. Every non-null pointer passed in must
+// reference `len` readable bytes owned by the host.
+#![allow(clippy::missing_safety_doc)]
+
+use crate::{Address, GemError, GemNFTContract, GemStat, MintRequest, RarityConfig, Royalty};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// Size of the length prefix in front of every result
+const LEN_PREFIX: usize = 4;
+
+// Allocate `len` bytes for the host to write call inputs into
+#[no_mangle]
+pub extern "C" fn alloc(len: usize) -> *mut u8 {
+    Box::into_raw(vec![0u8; len].into_boxed_slice()) as *mut u8
+}
+
+// Free a buffer returned by `alloc` or by any export (`len` includes the prefix)
+#[no_mangle]
+pub unsafe extern "C" fn dealloc(ptr: *mut u8, len: usize) {
+    if ptr.is_null() {
+        return;
+    }
+    drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(ptr, len)));
+}
+
+// Hand a length-prefixed JSON buffer to the host, which must `dealloc` it
+fn into_ptr(value: serde_json::Value) -> *mut u8 {
+    let json = serde_json::to_vec(&value).unwrap_or_else(|_| {
+        br#"{"ok":false,"error":{"code":1021,"message":"Result could not be encoded"}}"#.to_vec()
+    });
+    let mut bytes = Vec::with_capacity(LEN_PREFIX + json.len());
+    bytes.extend_from_slice(&(json.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&json);
+    Box::into_raw(bytes.into_boxed_slice()) as *mut u8
+}
+
+fn error_envelope(error: GemError) -> *mut u8 {
+    into_ptr(serde_json::json!({ "ok": false, "error": error }))
+}
+
+// Borrow a host buffer as UTF-8 text; `input` names it in errors
+unsafe fn read_str<'a>(ptr: *const u8, len: usize, input: &str) -> Result<&'a str, GemError> {
+    if len == 0 {
+        return Ok("");
+    }
+    if ptr.is_null() {
+        return Err(GemError::InvalidArgs(format!("{} pointer is null", input)));
+    }
+
+    let bytes = std::slice::from_raw_parts(ptr, len);
+    std::str::from_utf8(bytes).map_err(|_| GemError::InvalidUtf8(input.to_string()))
+}
+
+unsafe fn read_args<T: DeserializeOwned>(ptr: *const u8, len: usize) -> Result<T, GemError> {
+    let json = read_str(ptr, len, "args")?;
+    serde_json::from_str(json).map_err(|e| GemError::InvalidArgs(e.to_string()))
+}
+
+unsafe fn load_state(ptr: *const u8, len: usize) -> Result<GemNFTContract, GemError> {
+    let json = read_str(ptr, len, "state")?;
+    let mut contract: GemNFTContract = serde_json::from_str(json)
+        .map_err(|e| GemError::InvalidState(e.to_string()))?;
+    contract.migrate();
+    contract.validate_state()?;
+    Ok(contract)
+}
+
+// Apply a state-changing call and return the new state with the events it raised
+unsafe fn call_mut<A, R, F>(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+    f: F,
+) -> *mut u8
+where
+    A: DeserializeOwned,
+    R: Serialize,
+    F: FnOnce(&mut GemNFTContract, A) -> Result<R, GemError>,
+{
+    let mut contract = match load_state(state_ptr, state_len) {
+        Ok(contract) => contract,
+        Err(error) => return error_envelope(error),
+    };
+    let args: A = match read_args(args_ptr, args_len) {
+        Ok(args) => args,
+        Err(error) => return error_envelope(error),
+    };
+
+    let envelope = match f(&mut contract, args) {
+        Ok(result) => {
+            let events = contract.take_events();
+            serde_json::json!({
+                "ok": true,
+                "state": contract,
+                "result": result,
+                "events": events
+            })
+        }
+        Err(error) => serde_json::json!({ "ok": false, "error": error }),
+    };
+
+    into_ptr(envelope)
+}
+
+// Run a read-only query against the state
+unsafe fn call_view<A, R, F>(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+    f: F,
+) -> *mut u8
+where
+    A: DeserializeOwned,
+    R: Serialize,
+    F: FnOnce(&GemNFTContract, A) -> R,
+{
+    try_call_view(state_ptr, state_len, args_ptr, args_len, |c, a| Ok(f(c, a)))
+}
+
+// Run a read-only query that can reject its arguments
+unsafe fn try_call_view<A, R, F>(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+    f: F,
+) -> *mut u8
+where
+    A: DeserializeOwned,
+    R: Serialize,
+    F: FnOnce(&GemNFTContract, A) -> Result<R, GemError>,
+{
+    let contract = match load_state(state_ptr, state_len) {
+        Ok(contract) => contract,
+        Err(error) => return error_envelope(error),
+    };
+    let args: A = match read_args(args_ptr, args_len) {
+        Ok(args) => args,
+        Err(error) => return error_envelope(error),
+    };
+
+    let envelope = match f(&contract, args) {
+        Ok(result) => serde_json::json!({ "ok": true, "result": result }),
+        Err(error) => serde_json::json!({ "ok": false, "error": error }),
+    };
+
+    into_ptr(envelope)
+}
+
+#[derive(Deserialize)]
+struct InitArgs {
+    contract_owner: Address,
+}
+
+#[derive(Deserialize)]
+struct LegacyStateArgs {
+    contract_owner: Address,
+    #[serde(default)]
+    addresses: HashMap<String, Address>,
+}
+
+#[derive(Deserialize)]
+struct MintArgs {
+    name: String,
+    owner: Address,
+    caller: Address,
+    block_hash: String,
+    #[serde(default)]
+    metadata_uri: String,
+    #[serde(default)]
+    image_uri: String,
+    timestamp: u64,
+}
+
+#[derive(Deserialize)]
+struct MintRequestArgs {
+    request: MintRequest,
+    caller: Address,
+}
+
+#[derive(Deserialize)]
+struct MintBatchArgs {
+    requests: Vec<MintRequest>,
+    caller: Address,
+}
+
+#[derive(Deserialize)]
+struct CreateCollectionArgs {
+    name: String,
+    #[serde(default)]
+    max_supply: Option<u64>,
+    #[serde(default)]
+    base_uri: String,
+    #[serde(default)]
+    royalty: Option<Royalty>,
+    caller: Address,
+}
+
+#[derive(Deserialize)]
+struct AccountArgs {
+    account: Address,
+    caller: Address,
+}
+
+#[derive(Deserialize)]
+struct MaxSupplyArgs {
+    max_supply: Option<u64>,
+    caller: Address,
+}
+
+#[derive(Deserialize)]
+struct MintLimitArgs {
+    account: Address,
+    limit: Option<u64>,
+    caller: Address,
+}
+
+#[derive(Deserialize)]
+struct FuseArgs {
+    gem_ids: Vec<String>,
+    caller: Address,
+    timestamp: u64,
+}
+
+#[derive(Deserialize)]
+struct RarityConfigArgs {
+    config: RarityConfig,
+    caller: Address,
+}
+
+#[derive(Deserialize)]
+struct TransferArgs {
+    gem_id: String,
+    from: Address,
+    to: Address,
+    timestamp: u64,
+}
+
+#[derive(Deserialize)]
+struct TransferBatchArgs {
+    from: Address,
+    transfers: Vec<(String, Address)>,
+    timestamp: u64,
+}
+
+#[derive(Deserialize)]
+struct TransferFromArgs {
+    gem_id: String,
+    caller: Address,
+    from: Address,
+    to: Address,
+    timestamp: u64,
+}
+
+#[derive(Deserialize)]
+struct ApproveArgs {
+    gem_id: String,
+    spender: Address,
+    caller: Address,
+}
+
+#[derive(Deserialize)]
+struct ApprovalForAllArgs {
+    owner: Address,
+    operator: Address,
+    approved: bool,
+}
+
+#[derive(Deserialize)]
+struct SetUserArgs {
+    gem_id: String,
+    user: Address,
+    expires_at: u64,
+    caller: Address,
+    now: u64,
+}
+
+#[derive(Deserialize)]
+struct GemTimeArgs {
+    gem_id: String,
+    now: u64,
+}
+
+#[derive(Deserialize)]
+struct ProvenanceCapArgs {
+    cap: usize,
+    caller: Address,
+}
+
+#[derive(Deserialize)]
+struct GemCallerArgs {
+    gem_id: String,
+    caller: Address,
+}
+
+#[derive(Deserialize)]
+struct GemCallerTimeArgs {
+    gem_id: String,
+    caller: Address,
+    now: u64,
+}
+
+#[derive(Deserialize)]
+struct UpgradeArgs {
+    gem_id: String,
+    stat: GemStat,
+    amount: u32,
+    caller: Address,
+}
+
+#[derive(Deserialize)]
+struct MetadataUriArgs {
+    gem_id: String,
+    metadata_uri: String,
+    caller: Address,
+}
+
+#[derive(Deserialize)]
+struct PausedArgs {
+    paused: bool,
+    caller: Address,
+}
+
+#[derive(Deserialize)]
+struct NoArgs {}
+
+#[derive(Deserialize)]
+struct GemArgs {
+    gem_id: String,
+}
+
+#[derive(Deserialize)]
+struct OwnerArgs {
+    owner: Address,
+}
+
+#[derive(Deserialize)]
+struct OwnerTimeArgs {
+    owner: Address,
+    now: u64,
+}
+
+#[derive(Deserialize)]
+struct IndexArgs {
+    index: usize,
+}
+
+#[derive(Deserialize)]
+struct OwnerIndexArgs {
+    owner: Address,
+    index: usize,
+}
+
+#[derive(Deserialize)]
+struct PageArgs {
+    #[serde(default)]
+    cursor: Option<String>,
+    limit: usize,
+}
+
+#[derive(Deserialize)]
+struct OwnerPageArgs {
+    owner: Address,
+    #[serde(default)]
+    cursor: Option<String>,
+    limit: usize,
+}
+
+#[derive(Deserialize)]
+struct AddressArgs {
+    address: Address,
+}
+
+#[derive(Deserialize)]
+struct GemAddressArgs {
+    gem_id: String,
+    address: Address,
+}
+
+#[derive(Deserialize)]
+struct OperatorArgs {
+    owner: Address,
+    operator: Address,
+}
+
+#[derive(Deserialize)]
+struct RoyaltyInfoArgs {
+    gem_id: String,
+    sale_price: f64,
+}
+
+#[derive(Deserialize)]
+struct CollectionArgs {
+    collection_id: String,
+}
+
+// Create a fresh contract state
+#[no_mangle]
+pub unsafe extern "C" fn init(args_ptr: *const u8, args_len: usize) -> *mut u8 {
+    let args: InitArgs = match read_args(args_ptr, args_len) {
+        Ok(args) => args,
+        Err(error) => return error_envelope(error),
+    };
+    let contract = GemNFTContract::new(args.contract_owner);
+
+    into_ptr(serde_json::json!({
+        "ok": true,
+        "state": contract,
+        "result": null,
+        "events": []
+    }))
+}
+
+// Convert state with free-form owner strings, mapping each to a wallet address
+#[no_mangle]
+pub unsafe extern "C" fn migrate_legacy_state(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    let args: LegacyStateArgs = match read_args(args_ptr, args_len) {
+        Ok(args) => args,
+        Err(error) => return error_envelope(error),
+    };
+    let contract = match read_str(state_ptr, state_len, "state").and_then(|json| {
+        GemNFTContract::from_legacy_state(json, args.contract_owner, &args.addresses)
+    }) {
+        Ok(contract) => contract,
+        Err(error) => return error_envelope(error),
+    };
+
+    into_ptr(serde_json::json!({
+        "ok": true,
+        "state": contract,
+        "result": null,
+        "events": []
+    }))
+}
+
+// Mint a gem with attributes rolled from the host-supplied block hash
+#[no_mangle]
+pub unsafe extern "C" fn mint(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_mut(state_ptr, state_len, args_ptr, args_len, |c, a: MintArgs| {
+        // Derive attributes from chain data so every node computes the same gem
+        let seed = crate::mint_seed(&a.caller, c.total_minted(), a.block_hash.as_bytes());
+        let attributes = c.roll_attributes(seed);
+        let request = MintRequest {
+            name: a.name,
+            owner: a.owner,
+            attributes,
+            metadata_uri: a.metadata_uri,
+            timestamp: a.timestamp,
+            royalty: None,
+            collection_id: None,
+            soulbound: false,
+            image_uri: a.image_uri,
+        };
+        c.mint_request(request, &a.caller)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn mint_request(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_mut(state_ptr, state_len, args_ptr, args_len, |c, a: MintRequestArgs| {
+        c.mint_request(a.request, &a.caller)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn mint_batch(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_mut(state_ptr, state_len, args_ptr, args_len, |c, a: MintBatchArgs| {
+        c.mint_batch(a.requests, &a.caller)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn create_collection(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_mut(state_ptr, state_len, args_ptr, args_len, |c, a: CreateCollectionArgs| {
+        c.create_collection(a.name, a.max_supply, a.base_uri, a.royalty, &a.caller)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn add_minter(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_mut(state_ptr, state_len, args_ptr, args_len, |c, a: AccountArgs| {
+        c.add_minter(a.account, &a.caller)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn remove_minter(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_mut(state_ptr, state_len, args_ptr, args_len, |c, a: AccountArgs| {
+        c.remove_minter(&a.account, &a.caller)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn add_usage_reporter(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_mut(state_ptr, state_len, args_ptr, args_len, |c, a: AccountArgs| {
+        c.add_usage_reporter(a.account, &a.caller)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn remove_usage_reporter(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_mut(state_ptr, state_len, args_ptr, args_len, |c, a: AccountArgs| {
+        c.remove_usage_reporter(&a.account, &a.caller)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn set_max_supply(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_mut(state_ptr, state_len, args_ptr, args_len, |c, a: MaxSupplyArgs| {
+        c.set_max_supply(a.max_supply, &a.caller)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn set_mint_limit(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_mut(state_ptr, state_len, args_ptr, args_len, |c, a: MintLimitArgs| {
+        c.set_mint_limit(&a.account, a.limit, &a.caller)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn fuse(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_mut(state_ptr, state_len, args_ptr, args_len, |c, a: FuseArgs| {
+        c.fuse(&a.gem_ids, &a.caller, a.timestamp)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn set_rarity_config(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_mut(state_ptr, state_len, args_ptr, args_len, |c, a: RarityConfigArgs| {
+        c.set_rarity_config(a.config, &a.caller)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn transfer(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_mut(state_ptr, state_len, args_ptr, args_len, |c, a: TransferArgs| {
+        c.transfer(&a.gem_id, &a.from, a.to, a.timestamp)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn transfer_batch(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_mut(state_ptr, state_len, args_ptr, args_len, |c, a: TransferBatchArgs| {
+        c.transfer_batch(&a.from, a.transfers, a.timestamp)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn transfer_from(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_mut(state_ptr, state_len, args_ptr, args_len, |c, a: TransferFromArgs| {
+        c.transfer_from(&a.gem_id, &a.caller, &a.from, a.to, a.timestamp)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn settle_sale(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_mut(state_ptr, state_len, args_ptr, args_len, |c, a: TransferFromArgs| {
+        c.settle_sale(&a.gem_id, &a.caller, &a.from, a.to, a.timestamp)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn approve(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_mut(state_ptr, state_len, args_ptr, args_len, |c, a: ApproveArgs| {
+        c.approve(&a.gem_id, a.spender, &a.caller)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn set_approval_for_all(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_mut(state_ptr, state_len, args_ptr, args_len, |c, a: ApprovalForAllArgs| {
+        c.set_approval_for_all(&a.owner, a.operator, a.approved)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn set_user(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_mut(state_ptr, state_len, args_ptr, args_len, |c, a: SetUserArgs| {
+        c.set_user(&a.gem_id, a.user, a.expires_at, &a.caller, a.now)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn set_provenance_cap(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_mut(state_ptr, state_len, args_ptr, args_len, |c, a: ProvenanceCapArgs| {
+        c.set_provenance_cap(a.cap, &a.caller)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn burn(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_mut(state_ptr, state_len, args_ptr, args_len, |c, a: GemCallerArgs| {
+        c.burn(&a.gem_id, &a.caller)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn stake(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_mut(state_ptr, state_len, args_ptr, args_len, |c, a: GemCallerTimeArgs| {
+        c.stake(&a.gem_id, &a.caller, a.now)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn unstake(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_mut(state_ptr, state_len, args_ptr, args_len, |c, a: GemCallerTimeArgs| {
+        c.unstake(&a.gem_id, &a.caller, a.now)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn upgrade(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_mut(state_ptr, state_len, args_ptr, args_len, |c, a: UpgradeArgs| {
+        c.upgrade(&a.gem_id, a.stat, a.amount, &a.caller)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn record_usage(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_mut(state_ptr, state_len, args_ptr, args_len, |c, a: GemCallerArgs| {
+        c.record_usage(&a.gem_id, &a.caller)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn repair(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_mut(state_ptr, state_len, args_ptr, args_len, |c, a: GemCallerTimeArgs| {
+        c.repair(&a.gem_id, &a.caller, a.now)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn set_metadata_uri(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_mut(state_ptr, state_len, args_ptr, args_len, |c, a: MetadataUriArgs| {
+        c.set_metadata_uri(&a.gem_id, a.metadata_uri, &a.caller)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn freeze_metadata(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_mut(state_ptr, state_len, args_ptr, args_len, |c, a: GemCallerArgs| {
+        c.freeze_metadata(&a.gem_id, &a.caller)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn set_paused(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_mut(state_ptr, state_len, args_ptr, args_len, |c, a: PausedArgs| {
+        c.set_paused(a.paused, &a.caller)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn get_gem(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_view(state_ptr, state_len, args_ptr, args_len, |c, a: GemArgs| {
+        c.get_gem(&a.gem_id).cloned()
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn get_gems_by_owner(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_view(state_ptr, state_len, args_ptr, args_len, |c, a: OwnerArgs| {
+        serde_json::json!(c.get_gems_by_owner(&a.owner))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn token_by_index(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_view(state_ptr, state_len, args_ptr, args_len, |c, a: IndexArgs| {
+        c.token_by_index(a.index).cloned()
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn token_of_owner_by_index(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_view(state_ptr, state_len, args_ptr, args_len, |c, a: OwnerIndexArgs| {
+        c.token_of_owner_by_index(&a.owner, a.index).cloned()
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn list_gems(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    try_call_view(state_ptr, state_len, args_ptr, args_len, |c, a: PageArgs| {
+        c.list_gems(a.cursor.as_deref(), a.limit).map(|page| serde_json::json!(page))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn list_gems_by_owner(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    try_call_view(state_ptr, state_len, args_ptr, args_len, |c, a: OwnerPageArgs| {
+        c.list_gems_by_owner(&a.owner, a.cursor.as_deref(), a.limit)
+            .map(|page| serde_json::json!(page))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn total_supply(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_view(state_ptr, state_len, args_ptr, args_len, |c, _: NoArgs| c.total_supply())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn total_minted(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_view(state_ptr, state_len, args_ptr, args_len, |c, _: NoArgs| c.total_minted())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn is_owner(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_view(state_ptr, state_len, args_ptr, args_len, |c, a: GemAddressArgs| {
+        c.is_owner(&a.gem_id, &a.address)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn is_approved_or_owner(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_view(state_ptr, state_len, args_ptr, args_len, |c, a: GemAddressArgs| {
+        c.is_approved_or_owner(&a.gem_id, &a.address)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn get_approved(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_view(state_ptr, state_len, args_ptr, args_len, |c, a: GemArgs| {
+        c.get_approved(&a.gem_id).cloned()
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn is_approved_for_all(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_view(state_ptr, state_len, args_ptr, args_len, |c, a: OperatorArgs| {
+        c.is_approved_for_all(&a.owner, &a.operator)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn user_of(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_view(state_ptr, state_len, args_ptr, args_len, |c, a: GemTimeArgs| {
+        c.user_of(&a.gem_id, a.now).cloned()
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn user_expires(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_view(state_ptr, state_len, args_ptr, args_len, |c, a: GemArgs| {
+        c.user_expires(&a.gem_id)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn current_durability(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_view(state_ptr, state_len, args_ptr, args_len, |c, a: GemTimeArgs| {
+        c.current_durability(&a.gem_id, a.now)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn is_cracked(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_view(state_ptr, state_len, args_ptr, args_len, |c, a: GemTimeArgs| {
+        c.is_cracked(&a.gem_id, a.now)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn is_staked(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_view(state_ptr, state_len, args_ptr, args_len, |c, a: GemArgs| {
+        c.is_staked(&a.gem_id)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn pending_rewards(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_view(state_ptr, state_len, args_ptr, args_len, |c, a: OwnerTimeArgs| {
+        c.pending_rewards(&a.owner, a.now)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn is_minter(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_view(state_ptr, state_len, args_ptr, args_len, |c, a: AddressArgs| {
+        c.is_minter(&a.address)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn is_usage_reporter(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_view(state_ptr, state_len, args_ptr, args_len, |c, a: AddressArgs| {
+        c.is_usage_reporter(&a.address)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn royalty_info(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_view(state_ptr, state_len, args_ptr, args_len, |c, a: RoyaltyInfoArgs| {
+        c.royalty_info(&a.gem_id, a.sale_price)
+            .map(|(recipient, amount)| serde_json::json!({ "recipient": recipient, "amount": amount }))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn token_metadata(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_view(state_ptr, state_len, args_ptr, args_len, |c, a: GemArgs| {
+        c.token_metadata(&a.gem_id)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn get_provenance(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_view(state_ptr, state_len, args_ptr, args_len, |c, a: GemArgs| {
+        c.get_provenance(&a.gem_id).map(|entries| entries.to_vec())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn get_collection(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_view(state_ptr, state_len, args_ptr, args_len, |c, a: CollectionArgs| {
+        c.get_collection(&a.collection_id).cloned()
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn get_gems_by_collection(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_view(state_ptr, state_len, args_ptr, args_len, |c, a: CollectionArgs| {
+        serde_json::json!(c.get_gems_by_collection(&a.collection_id))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn collection_supply(
+    state_ptr: *const u8,
+    state_len: usize,
+    args_ptr: *const u8,
+    args_len: usize,
+) -> *mut u8 {
+    call_view(state_ptr, state_len, args_ptr, args_len, |c, a: CollectionArgs| {
+        c.collection_supply(&a.collection_id)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+
+    type Export = unsafe extern "C" fn(*const u8, usize, *const u8, usize) -> *mut u8;
+
+    const ADMIN: &str = "fdf795f176e75bca";
+    const ALICE: &str = "48175356aa0b5e87";
+    const BOB: &str = "ea6b9a9e64a15d55";
+    const CAROL: &str = "570ea4a8b5ec34a1";
+    const DAVE: &str = "d2a6f8e1c0b74539";
+    const ERIN: &str = "e71b5c0a94d3f26e";
+    const STUDIO: &str = "5d10a7c3e9b24f68";
+    const MARKET: &str = "3a7f0c91d2e4b856";
+    const MALLORY: &str = "9e0d4c2b7a6f1835";
+
+    // Copy bytes into a contract-allocated buffer, as the host does
+    fn write_input(bytes: &[u8]) -> (*mut u8, usize) {
+        let ptr = alloc(bytes.len());
+        unsafe { std::ptr::copy_nonoverlapping(bytes.as_ptr(), ptr, bytes.len()) };
+        (ptr, bytes.len())
+    }
+
+    // Read a length-prefixed result and free it
+    unsafe fn take_result(ptr: *mut u8) -> Value {
+        let mut prefix = [0u8; LEN_PREFIX];
+        std::ptr::copy_nonoverlapping(ptr, prefix.as_mut_ptr(), LEN_PREFIX);
+        let len = u32::from_le_bytes(prefix) as usize;
+
+        let json = std::slice::from_raw_parts(ptr.add(LEN_PREFIX), len);
+        let value = serde_json::from_slice(json).unwrap();
+        dealloc(ptr, LEN_PREFIX + len);
+        value
+    }
+
+    // Call an export through raw pointers and decode its envelope
+    fn call(export: Export, state: &Value, args: Value) -> Value {
+        let (state_ptr, state_len) = write_input(&serde_json::to_vec(state).unwrap());
+        let (args_ptr, args_len) = write_input(&serde_json::to_vec(&args).unwrap());
+        unsafe {
+            let result = take_result(export(state_ptr, state_len, args_ptr, args_len));
+            dealloc(state_ptr, state_len);
+            dealloc(args_ptr, args_len);
+            result
+        }
+    }
+
+    // Call a mutating export, assert success and return (new state, result)
+    fn call_ok(export: Export, state: &Value, args: Value) -> (Value, Value) {
+        let envelope = call(export, state, args);
+        assert_eq!(envelope["ok"], true, "{}", envelope);
+        (envelope["state"].clone(), envelope["result"].clone())
+    }
+
+    fn query(export: Export, state: &Value, args: Value) -> Value {
+        let envelope = call(export, state, args);
+        assert_eq!(envelope["ok"], true, "{}", envelope);
+        assert!(envelope.get("state").is_none());
+        envelope["result"].clone()
+    }
+
+    fn init_state() -> Value {
+        let args = serde_json::to_vec(&json!({ "contract_owner": ADMIN })).unwrap();
+        let envelope: Value = unsafe { take_result(init(args.as_ptr(), args.len())) };
+        assert_eq!(envelope["ok"], true);
+        envelope["state"].clone()
+    }
+
+    fn mint_args(owner: &str) -> Value {
+        json!({
+            "name": "Ruby",
+            "owner": owner,
+            "caller": ADMIN,
+            "block_hash": "00ff",
+            "metadata_uri": "ipfs://ruby",
+            "image_uri": "ipfs://ruby.png",
+            "timestamp": 100
+        })
+    }
+
+    #[test]
+    fn test_mint_and_query_exports() {
+        let state = init_state();
+
+        let envelope = call(mint, &state, mint_args(ALICE));
+        assert_eq!(envelope["ok"], true);
+        assert_eq!(envelope["result"], "GEM-0");
+        assert_eq!(envelope["events"][0]["type"], "Minted");
+        let state = envelope["state"].clone();
+
+        // Non-minters get an error envelope and no state
+        let mut bad = mint_args(ALICE);
+        bad["caller"] = json!(MALLORY);
+        let envelope = call(mint, &state, bad);
+        assert_eq!(envelope["ok"], false);
+        assert_eq!(envelope["error"]["code"], 1005);
+        assert!(envelope.get("state").is_none());
+
+        let gem = query(get_gem, &state, json!({ "gem_id": "GEM-0" }));
+        assert_eq!(gem["owner"], ALICE);
+        assert_eq!(query(get_gem, &state, json!({ "gem_id": "GEM-9" })), Value::Null);
+        assert_eq!(query(get_gems_by_owner, &state, json!({ "owner": ALICE })).as_array().unwrap().len(), 1);
+        assert_eq!(query(total_supply, &state, json!({})), 1);
+        assert_eq!(query(total_minted, &state, json!({})), 1);
+        assert_eq!(query(token_by_index, &state, json!({ "index": 0 })), "GEM-0");
+        assert_eq!(query(token_of_owner_by_index, &state, json!({ "owner": ALICE, "index": 1 })), Value::Null);
+        let page = query(list_gems, &state, json!({ "limit": 10 }));
+        assert_eq!(page["gems"][0]["id"], "GEM-0");
+        assert_eq!(page["next_cursor"], Value::Null);
+        let page = query(list_gems_by_owner, &state, json!({ "owner": ALICE, "cursor": "GEM-0", "limit": 10 }));
+        assert_eq!(page["gems"], json!([]));
+        let envelope = call(list_gems, &state, json!({ "cursor": "GEM-x", "limit": 10 }));
+        assert_eq!(envelope["error"]["code"], 1022);
+        assert_eq!(query(is_owner, &state, json!({ "gem_id": "GEM-0", "address": ALICE })), true);
+        assert_eq!(query(is_minter, &state, json!({ "address": ADMIN })), true);
+        assert_eq!(
+            query(royalty_info, &state, json!({ "gem_id": "GEM-0", "sale_price": 100.0 })),
+            json!({ "recipient": ALICE, "amount": 5.0 })
+        );
+        let metadata = query(token_metadata, &state, json!({ "gem_id": "GEM-0" }));
+        assert_eq!(metadata["name"], "Ruby");
+        assert_eq!(metadata["image"], "ipfs://ruby.png");
+        assert_eq!(query(get_provenance, &state, json!({ "gem_id": "GEM-0" }))[0]["reason"], "Mint");
+        assert_eq!(query(get_collection, &state, json!({ "collection_id": "default" }))["creator"], ADMIN);
+        assert_eq!(query(get_gems_by_collection, &state, json!({ "collection_id": "default" }))[0]["id"], "GEM-0");
+        assert_eq!(query(collection_supply, &state, json!({ "collection_id": "default" })), 1);
+    }
+
+    #[test]
+    fn test_transfer_and_approval_exports() {
+        let (state, _) = call_ok(mint, &init_state(), mint_args(ALICE));
+        let (state, _) = call_ok(mint, &state, mint_args(ALICE));
+
+        let (state, _) = call_ok(transfer, &state, json!({ "gem_id": "GEM-0", "from": ALICE, "to": BOB, "timestamp": 200 }));
+        assert_eq!(query(is_owner, &state, json!({ "gem_id": "GEM-0", "address": BOB })), true);
+
+        let (state, _) = call_ok(approve, &state, json!({ "gem_id": "GEM-1", "spender": MARKET, "caller": ALICE }));
+        assert_eq!(query(get_approved, &state, json!({ "gem_id": "GEM-1" })), MARKET);
+        assert_eq!(query(is_approved_or_owner, &state, json!({ "gem_id": "GEM-1", "address": MARKET })), true);
+
+        let (state, _) = call_ok(settle_sale, &state, json!({ "gem_id": "GEM-1", "caller": MARKET, "from": ALICE, "to": CAROL, "timestamp": 300 }));
+        assert_eq!(query(get_provenance, &state, json!({ "gem_id": "GEM-1" }))[1]["reason"], "Sale");
+
+        let (state, _) = call_ok(set_user, &state, json!({ "gem_id": "GEM-1", "user": DAVE, "expires_at": 500, "caller": CAROL, "now": 300 }));
+        assert_eq!(query(user_of, &state, json!({ "gem_id": "GEM-1", "now": 400 })), DAVE);
+        assert_eq!(query(user_expires, &state, json!({ "gem_id": "GEM-1" })), 500);
+
+        let (state, _) = call_ok(set_approval_for_all, &state, json!({ "owner": CAROL, "operator": MARKET, "approved": true }));
+        assert_eq!(query(is_approved_for_all, &state, json!({ "owner": CAROL, "operator": MARKET })), true);
+
+        let (state, _) = call_ok(transfer_from, &state, json!({ "gem_id": "GEM-1", "caller": MARKET, "from": CAROL, "to": DAVE, "timestamp": 400 }));
+        assert_eq!(query(user_of, &state, json!({ "gem_id": "GEM-1", "now": 400 })), Value::Null);
+        // Time-dependent calls need the host's clock
+        let envelope = call(transfer_batch, &state, json!({ "from": DAVE, "transfers": [["GEM-1", ERIN]] }));
+        assert_eq!(envelope["error"]["code"], 1022);
+        let (state, _) = call_ok(transfer_batch, &state, json!({ "from": DAVE, "transfers": [["GEM-1", ERIN]], "timestamp": 500 }));
+        assert_eq!(query(is_owner, &state, json!({ "gem_id": "GEM-1", "address": ERIN })), true);
+
+        let (state, _) = call_ok(burn, &state, json!({ "gem_id": "GEM-1", "caller": ERIN }));
+        assert_eq!(query(total_supply, &state, json!({})), 1);
+
+        let (state, _) = call_ok(stake, &state, json!({ "gem_id": "GEM-0", "caller": BOB, "now": 0 }));
+        assert_eq!(query(is_staked, &state, json!({ "gem_id": "GEM-0" })), true);
+        assert!(query(pending_rewards, &state, json!({ "owner": BOB, "now": 3600 })).as_u64().unwrap() > 0);
+        let envelope = call(transfer, &state, json!({ "gem_id": "GEM-0", "from": BOB, "to": ALICE, "timestamp": 600 }));
+        assert_eq!(envelope["error"]["code"], 1026);
+        let (state, points) = call_ok(unstake, &state, json!({ "gem_id": "GEM-0", "caller": BOB, "now": 36000 }));
+        assert!(points.as_u64().unwrap() > 0);
+
+        let envelope = call(upgrade, &state, json!({ "gem_id": "GEM-0", "stat": "Power", "amount": 0, "caller": BOB }));
+        assert_eq!(envelope["error"]["code"], 1028);
+
+        let durability = query(current_durability, &state, json!({ "gem_id": "GEM-0", "now": 100 }));
+        let envelope = call(record_usage, &state, json!({ "gem_id": "GEM-0", "caller": STUDIO }));
+        assert_eq!(envelope["error"]["code"], 1036);
+        let (state, _) = call_ok(add_usage_reporter, &state, json!({ "account": STUDIO, "caller": ADMIN }));
+        assert_eq!(query(is_usage_reporter, &state, json!({ "address": STUDIO })), true);
+        let (state, _) = call_ok(record_usage, &state, json!({ "gem_id": "GEM-0", "caller": STUDIO }));
+        let (state, _) = call_ok(remove_usage_reporter, &state, json!({ "account": STUDIO, "caller": ADMIN }));
+        assert_eq!(query(is_usage_reporter, &state, json!({ "address": STUDIO })), false);
+        assert!(query(current_durability, &state, json!({ "gem_id": "GEM-0", "now": 100 })).as_u64() < durability.as_u64());
+        let (state, _) = call_ok(repair, &state, json!({ "gem_id": "GEM-0", "caller": BOB, "now": 100 }));
+        assert_eq!(query(current_durability, &state, json!({ "gem_id": "GEM-0", "now": 100 })), durability);
+        assert_eq!(query(is_cracked, &state, json!({ "gem_id": "GEM-0", "now": 100 })), false);
+    }
+
+    #[test]
+    fn test_admin_exports() {
+        let state = init_state();
+
+        let (state, _) = call_ok(add_minter, &state, json!({ "account": STUDIO, "caller": ADMIN }));
+        let (state, collection_id) = call_ok(create_collection, &state, json!({ "name": "Volcanic", "max_supply": 5, "caller": STUDIO }));
+        assert_eq!(collection_id, "COLLECTION-0");
+
+        let request = json!({
+            "name": "Obsidian",
+            "owner": ALICE,
+            "attributes": { "color": "Black", "rarity": "Common", "power": 10, "shine": 10, "durability": 10 },
+            "metadata_uri": "ipfs://obsidian",
+            "timestamp": 0,
+            "collection_id": "COLLECTION-0"
+        });
+        let (state, _) = call_ok(mint_request, &state, json!({ "request": request, "caller": STUDIO }));
+        let (state, ids) = call_ok(mint_batch, &state, json!({ "requests": [request, request], "caller": STUDIO }));
+        assert_eq!(ids, json!(["GEM-1", "GEM-2"]));
+
+        let (state, fused) = call_ok(fuse, &state, json!({ "gem_ids": ["GEM-0", "GEM-1"], "caller": ALICE, "timestamp": 100 }));
+        assert_eq!(fused, "GEM-3");
+
+        let (state, _) = call_ok(set_mint_limit, &state, json!({ "account": STUDIO, "limit": 3, "caller": ADMIN }));
+        let (state, _) = call_ok(set_max_supply, &state, json!({ "max_supply": 100, "caller": ADMIN }));
+        let (state, _) = call_ok(set_provenance_cap, &state, json!({ "cap": 8, "caller": ADMIN }));
+        let config = serde_json::to_value(RarityConfig::default()).unwrap();
+        let (state, _) = call_ok(set_rarity_config, &state, json!({ "config": config, "caller": ADMIN }));
+        let (state, _) = call_ok(remove_minter, &state, json!({ "account": STUDIO, "caller": ADMIN }));
+        assert_eq!(query(is_minter, &state, json!({ "address": STUDIO })), false);
+
+        let (state, _) = call_ok(set_metadata_uri, &state, json!({ "gem_id": "GEM-2", "metadata_uri": "ipfs://fixed", "caller": ADMIN }));
+        assert_eq!(query(get_gem, &state, json!({ "gem_id": "GEM-2" }))["metadata_uri"], "ipfs://fixed");
+        let (state, _) = call_ok(freeze_metadata, &state, json!({ "gem_id": "GEM-2", "caller": STUDIO }));
+        let envelope = call(set_metadata_uri, &state, json!({ "gem_id": "GEM-2", "metadata_uri": "ipfs://late", "caller": ADMIN }));
+        assert_eq!(envelope["error"]["code"], 1033);
+
+        let (state, _) = call_ok(set_paused, &state, json!({ "paused": true, "caller": ADMIN }));
+        let envelope = call(transfer, &state, json!({ "gem_id": "GEM-2", "from": ALICE, "to": BOB, "timestamp": 200 }));
+        assert_eq!(envelope["error"]["code"], 1006);
+    }
+
+    #[test]
+    fn test_result_length_prefix() {
+        let state = init_state();
+        let state_bytes = serde_json::to_vec(&state).unwrap();
+        let args = br#"{"gem_id": "GEM-0"}"#;
+
+        unsafe {
+            let ptr = get_gem(state_bytes.as_ptr(), state_bytes.len(), args.as_ptr(), args.len());
+            let len = u32::from_le_bytes(*(ptr as *const [u8; 4])) as usize;
+            let json = std::slice::from_raw_parts(ptr.add(LEN_PREFIX), len);
+            assert_eq!(json, br#"{"ok":true,"result":null}"#);
+            dealloc(ptr, LEN_PREFIX + len);
+        }
+
+        // Zero-length and null buffers are safe to hand back
+        unsafe {
+            dealloc(alloc(0), 0);
+            dealloc(std::ptr::null_mut(), 16);
+        }
+    }
+
+    // Counts heap bytes held by each thread, so tests running in parallel don't disturb each other
+    struct CountingAllocator;
+
+    thread_local! {
+        static LIVE_BYTES: Cell<isize> = const { Cell::new(0) };
+    }
+
+    fn track(delta: isize) {
+        let _ = LIVE_BYTES.try_with(|live| live.set(live.get() + delta));
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            track(layout.size() as isize);
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            track(-(layout.size() as isize));
+            System.dealloc(ptr, layout)
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            track(new_size as isize - layout.size() as isize);
+            System.realloc(ptr, layout, new_size)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    #[test]
+    fn test_exports_free_every_buffer() {
+        let state = serde_json::to_vec(&call(mint, &init_state(), mint_args(ALICE))["state"]).unwrap();
+        let mut rejected = mint_args(ALICE);
+        rejected["caller"] = json!(MALLORY);
+
+        // A state change, an error envelope and a query
+        let cases: [(Export, Value); 3] = [
+            (mint, mint_args(ALICE)),
+            (mint, rejected),
+            (get_gem, json!({ "gem_id": "GEM-0" })),
+        ];
+
+        for (export, args) in cases {
+            let args = serde_json::to_vec(&args).unwrap();
+            for _ in 0..3 {
+                let before = LIVE_BYTES.with(Cell::get);
+                unsafe {
+                    let ptr = export(state.as_ptr(), state.len(), args.as_ptr(), args.len());
+                    let len = u32::from_le_bytes(*(ptr as *const [u8; LEN_PREFIX])) as usize;
+                    dealloc(ptr, LEN_PREFIX + len);
+                }
+                assert_eq!(LIVE_BYTES.with(Cell::get), before);
+            }
+        }
+    }
+
+    // Call an export with raw bytes instead of JSON values
+    fn call_raw(export: Export, state: &[u8], args: &[u8]) -> Value {
+        unsafe { take_result(export(state.as_ptr(), state.len(), args.as_ptr(), args.len())) }
+    }
+
+    fn error_code(envelope: &Value) -> u64 {
+        assert_eq!(envelope["ok"], false, "{}", envelope);
+        envelope["error"]["code"].as_u64().unwrap()
+    }
+
+    #[test]
+    fn test_migrate_legacy_state_export() {
+        // What the baseline `init` and `mint` exports wrote
+        let legacy = json!({
+            "gems": {
+                "GEM-0": {
+                    "id": "GEM-0",
+                    "name": "Ruby",
+                    "owner": "alice",
+                    "creator": "alice",
+                    "attributes": { "color": "Blue", "rarity": "Common", "power": 50, "shine": 70, "durability": 80 },
+                    "metadata_uri": "ipfs://...",
+                    "created_at": 0,
+                    "transfer_count": 0
+                }
+            },
+            "owner_gems": { "alice": ["GEM-0"] },
+            "total_supply": 1,
+            "contract_owner": "system"
+        });
+
+        assert_eq!(call(get_gem, &legacy, json!({ "gem_id": "GEM-0" }))["error"]["code"], 1021);
+        let envelope = call(migrate_legacy_state, &legacy, json!({ "contract_owner": ADMIN }));
+        assert_eq!(envelope["error"]["code"], 1024);
+
+        let args = json!({ "contract_owner": ADMIN, "addresses": { "alice": ALICE } });
+        let (state, _) = call_ok(migrate_legacy_state, &legacy, args);
+        assert_eq!(query(get_gem, &state, json!({ "gem_id": "GEM-0" }))["owner"], ALICE);
+        assert_eq!(query(is_minter, &state, json!({ "address": ADMIN })), true);
+    }
+
+    #[test]
+    fn test_corrupted_inputs_return_error_envelopes() {
+        let state = serde_json::to_vec(&init_state()).unwrap();
+        let args = br#"{"gem_id": "GEM-0", "caller": "48175356aa0b5e87"}"#;
+
+        // Malformed and truncated state
+        assert_eq!(error_code(&call_raw(burn, b"{not json", args)), 1021);
+        assert_eq!(error_code(&call_raw(burn, &state[..state.len() / 2], args)), 1021);
+        assert_eq!(error_code(&call_raw(get_gem, b"", args)), 1021);
+
+        // Bad UTF-8 in either input
+        assert_eq!(error_code(&call_raw(burn, &[0xff, 0xfe, 0x7b], args)), 1023);
+        let envelope = call_raw(mint, &state, b"{\"name\": \"\xc3\x28\"}");
+        assert_eq!(error_code(&envelope), 1023);
+        assert_eq!(envelope["error"]["message"], "Input is not valid UTF-8: args");
+
+        // Arguments of the wrong shape
+        assert_eq!(error_code(&call_raw(burn, &state, br#"{"gem_id": 7}"#)), 1022);
+        assert_eq!(error_code(&call_raw(transfer, &state, b"[]")), 1022);
+        assert_eq!(error_code(&call_raw(total_supply, &state, b"")), 1022);
+
+        // Malformed addresses are rejected before the contract sees them
+        let envelope = call(mint, &init_state(), mint_args("alice "));
+        assert_eq!(error_code(&envelope), 1022);
+        assert!(envelope["error"]["message"].as_str().unwrap().contains(r#"Invalid address: "alice ""#));
+
+        // Null pointers
+        let envelope = unsafe { take_result(get_gem(std::ptr::null(), 10, args.as_ptr(), args.len())) };
+        assert_eq!(error_code(&envelope), 1022);
+        let envelope = unsafe { take_result(init(std::ptr::null(), 3)) };
+        assert_eq!(error_code(&envelope), 1022);
+
+        // Domain errors come back in the same envelope
+        assert_eq!(error_code(&call_raw(burn, &state, args)), 1001);
+    }
+
+    #[test]
+    fn test_inconsistent_state_is_rejected() {
+        let mut state = init_state();
+        state["rarity_config"]["tiers"] = json!([]);
+        let envelope = call(mint, &state, mint_args(ALICE));
+        assert_eq!(error_code(&envelope), 1021);
+
+        let mut state = init_state();
+        state["provenance_cap"] = json!(0);
+        assert_eq!(error_code(&call(total_supply, &state, json!({}))), 1021);
+
+        let mut state = init_state();
+        state["burned_count"] = json!(5);
+        assert_eq!(error_code(&call(total_supply, &state, json!({}))), 1021);
+    }
+}
@@ -1,5 +1,191 @@
-use serde::{Deserialize, Serialize};
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize, Serializer};
+use std::borrow::Borrow;
 use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+// Errors returned by MarketplaceContract; codes are stable and safe to match on
+#[derive(Debug, Clone, PartialEq)]
+pub enum MarketError {
+    ListingNotFound,
+    ListingNotActive,
+    NotFixedPrice,
+    NotAuction,
+    InsufficientPayment,
+    ListingExpired,
+    AuctionExpired,
+    BidTooLow,
+    AuctionNotEnded,
+    NotSeller,
+    NoBalance,
+    InvalidPrice,
+    NotContractOwner,
+    Paused,
+    InvalidAddress(String),
+    Soulbound,
+    GemCracked,
+    InvalidRoyalty,
+    InvalidState(String),
+}
+
+impl MarketError {
+    pub fn code(&self) -> u32 {
+        match self {
+            MarketError::ListingNotFound => 2001,
+            MarketError::ListingNotActive => 2002,
+            MarketError::NotFixedPrice => 2003,
+            MarketError::NotAuction => 2004,
+            MarketError::InsufficientPayment => 2005,
+            MarketError::ListingExpired => 2006,
+            MarketError::AuctionExpired => 2007,
+            MarketError::BidTooLow => 2008,
+            MarketError::AuctionNotEnded => 2009,
+            MarketError::NotSeller => 2010,
+            MarketError::NoBalance => 2011,
+            MarketError::InvalidPrice => 2012,
+            MarketError::NotContractOwner => 2013,
+            MarketError::Paused => 2014,
+            MarketError::InvalidAddress(_) => 2015,
+            MarketError::Soulbound => 2016,
+            MarketError::GemCracked => 2017,
+            MarketError::InvalidRoyalty => 2018,
+            MarketError::InvalidState(_) => 2019,
+        }
+    }
+}
+
+impl fmt::Display for MarketError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            MarketError::ListingNotFound => "Listing not found",
+            MarketError::ListingNotActive => "Listing is not active",
+            MarketError::NotFixedPrice => "Not a fixed price listing",
+            MarketError::NotAuction => "Not an auction listing",
+            MarketError::InsufficientPayment => "Insufficient payment",
+            MarketError::ListingExpired => "Listing has expired",
+            MarketError::AuctionExpired => "Auction has expired",
+            MarketError::BidTooLow => "Bid must be higher than current bid",
+            MarketError::AuctionNotEnded => "Auction has not expired yet",
+            MarketError::NotSeller => "Only seller can cancel listing",
+            MarketError::NoBalance => "No balance to withdraw",
+            MarketError::InvalidPrice => "Price must be positive",
+            MarketError::NotContractOwner => "Only contract owner can do this",
+            MarketError::Paused => "Marketplace is paused",
+            MarketError::Soulbound => "Soulbound gems cannot be listed",
+            MarketError::GemCracked => "Cracked gems cannot be listed until repaired",
+            MarketError::InvalidRoyalty => "Royalty exceeds maximum",
+            MarketError::InvalidAddress(address) => {
+                return write!(f, "Invalid address: {:?}", address);
+            }
+            MarketError::InvalidState(reason) => {
+                return write!(f, "Invalid contract state: {}", reason);
+            }
+        };
+        write!(f, "{}", message)
+    }
+}
+
+// Serialized as `{code, message}` so hosts can match on the code
+impl Serialize for MarketError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("MarketError", 2)?;
+        state.serialize_field("code", &self.code())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.end()
+    }
+}
+
+// nchain wallet address: exactly 16 lowercase hex characters
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Address(String);
+
+impl Address {
+    pub const LEN: usize = 16;
+
+    // Validate an address exactly as given; no trimming or case folding
+    pub fn parse(address: &str) -> Result<Self, MarketError> {
+        let valid = address.len() == Self::LEN
+            && address.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'));
+
+        if !valid {
+            return Err(MarketError::InvalidAddress(address.to_string()));
+        }
+        Ok(Address(address.to_string()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for Address {
+    type Err = MarketError;
+
+    fn from_str(address: &str) -> Result<Self, Self::Err> {
+        Address::parse(address)
+    }
+}
+
+impl TryFrom<String> for Address {
+    type Error = MarketError;
+
+    fn try_from(address: String) -> Result<Self, Self::Error> {
+        Address::parse(&address)
+    }
+}
+
+impl From<Address> for String {
+    fn from(address: Address) -> String {
+        address.0
+    }
+}
+
+impl Borrow<str> for Address {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+// Gem details the host reads from the gem contract before listing. They are supplied by
+// the caller, so checking them here only catches honest mistakes; the gem contract's
+// `settle_sale` is what actually refuses to hand over soulbound or cracked gems
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GemStatus {
+    pub gem_id: String,
+    #[serde(default)]
+    pub soulbound: bool,
+    // No durability left at listing time
+    #[serde(default)]
+    pub cracked: bool,
+    // The gem's royalty as stored on the gem contract
+    #[serde(default)]
+    pub royalty: Option<Royalty>,
+}
+
+// Highest royalty a listing may carry, in basis points; matches the gem contract's cap
+pub const MAX_ROYALTY_BPS: u16 = 1_000;
+
+// Royalty recipient and rate, fixed on the listing when it is created
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Royalty {
+    pub recipient: Address,
+    pub bps: u16,
+}
+
+impl Royalty {
+    // Amount owed on a sale at `price`
+    pub fn amount(&self, price: f64) -> f64 {
+        price * self.bps as f64 / 10_000.0
+    }
+}
 
 // Listing types
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -21,14 +207,16 @@ pub enum ListingStatus {
 pub struct Listing {
     pub id: String,
     pub gem_id: String,
-    pub seller: String,
+    pub seller: Address,
     pub listing_type: ListingType,
     pub price: f64,
     pub status: ListingStatus,
     pub created_at: u64,
     pub expires_at: Option<u64>,
     pub highest_bid: Option<f64>,
-    pub highest_bidder: Option<String>,
+    pub highest_bidder: Option<Address>,
+    #[serde(default)]
+    pub royalty: Option<Royalty>,
 }
 
 // Sale record
@@ -37,8 +225,8 @@ pub struct Sale {
     pub id: String,
     pub listing_id: String,
     pub gem_id: String,
-    pub seller: String,
-    pub buyer: String,
+    pub seller: Address,
+    pub buyer: Address,
     pub price: f64,
     pub timestamp: u64,
     pub royalty_paid: f64,
@@ -52,14 +240,87 @@ pub struct MarketplaceContract {
     pub active_listings: Vec<String>,
     pub listing_counter: u64,
     pub sale_counter: u64,
-    pub contract_owner: String,
+    pub contract_owner: Address,
     pub marketplace_fee_percent: f64,
-    pub royalty_percent: f64,
-    pub escrow_balances: HashMap<String, f64>,
+    pub escrow_balances: HashMap<Address, f64>,
+    #[serde(default)]
+    pub paused: bool,
+}
+
+// Rewrite one legacy owner string as an address
+fn resolve_legacy(
+    value: &mut serde_json::Value,
+    addresses: &HashMap<String, Address>,
+) -> Result<(), MarketError> {
+    let legacy = value.as_str()
+        .ok_or_else(|| MarketError::InvalidState("address is not a string".to_string()))?;
+    let address = match addresses.get(legacy) {
+        Some(address) => address.clone(),
+        None => Address::parse(legacy)?,
+    };
+    *value = address.to_string().into();
+    Ok(())
 }
 
 impl MarketplaceContract {
-    pub fn new(contract_owner: String, marketplace_fee: f64, royalty: f64) -> Self {
+    // Load state written before addresses were validated, when accounts were free-form
+    // strings. `addresses` maps each legacy string to a wallet address; strings that
+    // already are addresses are kept
+    pub fn from_legacy_state(
+        json: &str,
+        addresses: &HashMap<String, Address>,
+    ) -> Result<Self, MarketError> {
+        let invalid = |reason: &str| MarketError::InvalidState(reason.to_string());
+
+        let mut state: serde_json::Value = serde_json::from_str(json)
+            .map_err(|e| MarketError::InvalidState(e.to_string()))?;
+        let fields = state.as_object_mut()
+            .ok_or_else(|| invalid("state is not an object"))?;
+
+        if let Some(owner) = fields.get_mut("contract_owner") {
+            resolve_legacy(owner, addresses)?;
+        }
+
+        if let Some(listings) = fields.get_mut("listings").and_then(|l| l.as_object_mut()) {
+            for listing in listings.values_mut() {
+                if let Some(seller) = listing.get_mut("seller") {
+                    resolve_legacy(seller, addresses)?;
+                }
+                if let Some(bidder) = listing.get_mut("highest_bidder").filter(|b| !b.is_null()) {
+                    resolve_legacy(bidder, addresses)?;
+                }
+            }
+        }
+
+        if let Some(sales) = fields.get_mut("sales_history").and_then(|s| s.as_array_mut()) {
+            for sale in sales {
+                for field in ["seller", "buyer"] {
+                    if let Some(account) = sale.get_mut(field) {
+                        resolve_legacy(account, addresses)?;
+                    }
+                }
+            }
+        }
+
+        // Several legacy strings may name the same wallet, so their balances are added up
+        if let Some(balances) = fields.get_mut("escrow_balances").and_then(|b| b.as_object_mut()) {
+            let mut merged: HashMap<String, f64> = HashMap::new();
+            for (legacy, balance) in std::mem::take(balances) {
+                let mut account = serde_json::Value::String(legacy);
+                resolve_legacy(&mut account, addresses)?;
+                let balance = balance.as_f64()
+                    .ok_or_else(|| invalid("balance is not a number"))?;
+                *merged.entry(account.as_str().unwrap_or_default().to_string()).or_insert(0.0) += balance;
+            }
+            for (account, balance) in merged {
+                balances.insert(account, balance.into());
+            }
+        }
+
+        serde_json::from_value(state).map_err(|e| MarketError::InvalidState(e.to_string()))
+    }
+
+    pub fn new(contract_owner: Address, marketplace_fee: f64) -> Self {
         Self {
             listings: HashMap::new(),
             sales_history: Vec::new(),
@@ -68,23 +329,55 @@ impl MarketplaceContract {
             sale_counter: 0,
             contract_owner,
             marketplace_fee_percent: marketplace_fee,
-            royalty_percent: royalty,
             escrow_balances: HashMap::new(),
+            paused: false,
+        }
+    }
+
+    // Halt or resume listing, buying, bidding and settlement (contract owner only)
+    pub fn set_paused(&mut self, paused: bool, caller: &Address) -> Result<(), MarketError> {
+        if *caller != self.contract_owner {
+            return Err(MarketError::NotContractOwner);
         }
+
+        self.paused = paused;
+        Ok(())
+    }
+
+    // Fail if the marketplace is paused
+    fn when_not_paused(&self) -> Result<(), MarketError> {
+        if self.paused {
+            return Err(MarketError::Paused);
+        }
+        Ok(())
     }
 
     // Create a new listing
     pub fn create_listing(
         &mut self,
-        gem_id: String,
-        seller: String,
+        gem: GemStatus,
+        seller: Address,
         listing_type: ListingType,
         price: f64,
         duration_secs: Option<u64>,
         timestamp: u64,
-    ) -> Result<String, String> {
+    ) -> Result<String, MarketError> {
+        self.when_not_paused()?;
+
+        if gem.soulbound {
+            return Err(MarketError::Soulbound);
+        }
+
+        if gem.cracked {
+            return Err(MarketError::GemCracked);
+        }
+
         if price <= 0.0 {
-            return Err("Price must be positive".to_string());
+            return Err(MarketError::InvalidPrice);
+        }
+
+        if gem.royalty.as_ref().is_some_and(|royalty| royalty.bps > MAX_ROYALTY_BPS) {
+            return Err(MarketError::InvalidRoyalty);
         }
 
         let listing_id = format!("LISTING-{}", self.listing_counter);
@@ -94,7 +387,7 @@ impl MarketplaceContract {
 
         let listing = Listing {
             id: listing_id.clone(),
-            gem_id: gem_id.clone(),
+            gem_id: gem.gem_id,
             seller: seller.clone(),
             listing_type,
             price,
@@ -103,6 +396,7 @@ impl MarketplaceContract {
             expires_at,
             highest_bid: None,
             highest_bidder: None,
+            royalty: gem.royalty,
         };
 
         self.listings.insert(listing_id.clone(), listing);
@@ -115,41 +409,44 @@ impl MarketplaceContract {
     pub fn buy(
         &mut self,
         listing_id: &str,
-        buyer: String,
+        buyer: Address,
         payment_amount: f64,
         timestamp: u64,
-        creator: String,
-    ) -> Result<String, String> {
+    ) -> Result<String, MarketError> {
+        self.when_not_paused()?;
+
         let listing = self.listings.get_mut(listing_id)
-            .ok_or_else(|| "Listing not found".to_string())?;
+            .ok_or(MarketError::ListingNotFound)?;
 
         if listing.status != ListingStatus::Active {
-            return Err("Listing is not active".to_string());
+            return Err(MarketError::ListingNotActive);
         }
 
         if listing.listing_type != ListingType::FixedPrice {
-            return Err("Not a fixed price listing".to_string());
+            return Err(MarketError::NotFixedPrice);
         }
 
         if payment_amount < listing.price {
-            return Err("Insufficient payment".to_string());
+            return Err(MarketError::InsufficientPayment);
         }
 
         if let Some(expires) = listing.expires_at {
             if timestamp > expires {
                 listing.status = ListingStatus::Expired;
-                return Err("Listing has expired".to_string());
+                return Err(MarketError::ListingExpired);
             }
         }
 
         // Calculate fees
         let marketplace_fee = listing.price * (self.marketplace_fee_percent / 100.0);
-        let royalty = listing.price * (self.royalty_percent / 100.0);
-        let seller_amount = listing.price - marketplace_fee - royalty;
+        let royalty_paid = listing.royalty.as_ref().map_or(0.0, |r| r.amount(listing.price));
+        let seller_amount = listing.price - marketplace_fee - royalty_paid;
 
         // Update escrow balances
         *self.escrow_balances.entry(listing.seller.clone()).or_insert(0.0) += seller_amount;
-        *self.escrow_balances.entry(creator).or_insert(0.0) += royalty;
+        if let Some(royalty) = &listing.royalty {
+            *self.escrow_balances.entry(royalty.recipient.clone()).or_insert(0.0) += royalty_paid;
+        }
         *self.escrow_balances.entry(self.contract_owner.clone()).or_insert(0.0) += marketplace_fee;
 
         // Record sale
@@ -164,7 +461,7 @@ impl MarketplaceContract {
             buyer: buyer.clone(),
             price: listing.price,
             timestamp,
-            royalty_paid: royalty,
+            royalty_paid,
         };
 
         self.sales_history.push(sale);
@@ -180,31 +477,33 @@ impl MarketplaceContract {
     pub fn place_bid(
         &mut self,
         listing_id: &str,
-        bidder: String,
+        bidder: Address,
         bid_amount: f64,
         timestamp: u64,
-    ) -> Result<(), String> {
+    ) -> Result<(), MarketError> {
+        self.when_not_paused()?;
+
         let listing = self.listings.get_mut(listing_id)
-            .ok_or_else(|| "Listing not found".to_string())?;
+            .ok_or(MarketError::ListingNotFound)?;
 
         if listing.status != ListingStatus::Active {
-            return Err("Listing is not active".to_string());
+            return Err(MarketError::ListingNotActive);
         }
 
         if listing.listing_type != ListingType::Auction {
-            return Err("Not an auction listing".to_string());
+            return Err(MarketError::NotAuction);
         }
 
         if let Some(expires) = listing.expires_at {
             if timestamp > expires {
                 listing.status = ListingStatus::Expired;
-                return Err("Auction has expired".to_string());
+                return Err(MarketError::AuctionExpired);
             }
         }
 
         let minimum_bid = listing.highest_bid.unwrap_or(listing.price);
         if bid_amount <= minimum_bid {
-            return Err("Bid must be higher than current bid".to_string());
+            return Err(MarketError::BidTooLow);
         }
 
         // Return previous bid to previous bidder
@@ -226,22 +525,23 @@ impl MarketplaceContract {
         &mut self,
         listing_id: &str,
         timestamp: u64,
-        creator: String,
-    ) -> Result<Option<String>, String> {
+    ) -> Result<Option<String>, MarketError> {
+        self.when_not_paused()?;
+
         let listing = self.listings.get_mut(listing_id)
-            .ok_or_else(|| "Listing not found".to_string())?;
+            .ok_or(MarketError::ListingNotFound)?;
 
         if listing.status != ListingStatus::Active {
-            return Err("Listing is not active".to_string());
+            return Err(MarketError::ListingNotActive);
         }
 
         if listing.listing_type != ListingType::Auction {
-            return Err("Not an auction listing".to_string());
+            return Err(MarketError::NotAuction);
         }
 
         if let Some(expires) = listing.expires_at {
             if timestamp < expires {
-                return Err("Auction has not expired yet".to_string());
+                return Err(MarketError::AuctionNotEnded);
             }
         }
 
@@ -249,12 +549,14 @@ impl MarketplaceContract {
         if let (Some(winner), Some(winning_bid)) = (&listing.highest_bidder, listing.highest_bid) {
             // Calculate fees
             let marketplace_fee = winning_bid * (self.marketplace_fee_percent / 100.0);
-            let royalty = winning_bid * (self.royalty_percent / 100.0);
-            let seller_amount = winning_bid - marketplace_fee - royalty;
+            let royalty_paid = listing.royalty.as_ref().map_or(0.0, |r| r.amount(winning_bid));
+            let seller_amount = winning_bid - marketplace_fee - royalty_paid;
 
             // Distribute funds
             *self.escrow_balances.entry(listing.seller.clone()).or_insert(0.0) += seller_amount;
-            *self.escrow_balances.entry(creator).or_insert(0.0) += royalty;
+            if let Some(royalty) = &listing.royalty {
+                *self.escrow_balances.entry(royalty.recipient.clone()).or_insert(0.0) += royalty_paid;
+            }
             *self.escrow_balances.entry(self.contract_owner.clone()).or_insert(0.0) += marketplace_fee;
 
             // Record sale
@@ -269,7 +571,7 @@ impl MarketplaceContract {
                 buyer: winner.clone(),
                 price: winning_bid,
                 timestamp,
-                royalty_paid: royalty,
+                royalty_paid,
             };
 
             self.sales_history.push(sale);
@@ -289,17 +591,17 @@ impl MarketplaceContract {
     pub fn cancel_listing(
         &mut self,
         listing_id: &str,
-        seller: &str,
-    ) -> Result<(), String> {
+        seller: &Address,
+    ) -> Result<(), MarketError> {
         let listing = self.listings.get_mut(listing_id)
-            .ok_or_else(|| "Listing not found".to_string())?;
+            .ok_or(MarketError::ListingNotFound)?;
 
-        if listing.seller != seller {
-            return Err("Only seller can cancel listing".to_string());
+        if listing.seller != *seller {
+            return Err(MarketError::NotSeller);
         }
 
         if listing.status != ListingStatus::Active {
-            return Err("Listing is not active".to_string());
+            return Err(MarketError::ListingNotActive);
         }
 
         // Return any bids if it's an auction
@@ -332,19 +634,19 @@ impl MarketplaceContract {
     }
 
     // Withdraw escrow balance
-    pub fn withdraw(&mut self, address: &str) -> Result<f64, String> {
+    pub fn withdraw(&mut self, address: &Address) -> Result<f64, MarketError> {
         let balance = self.escrow_balances.get(address).copied().unwrap_or(0.0);
 
         if balance <= 0.0 {
-            return Err("No balance to withdraw".to_string());
+            return Err(MarketError::NoBalance);
         }
 
-        self.escrow_balances.insert(address.to_string(), 0.0);
+        self.escrow_balances.insert(address.clone(), 0.0);
         Ok(balance)
     }
 
     // Get escrow balance
-    pub fn get_balance(&self, address: &str) -> f64 {
+    pub fn get_balance(&self, address: &Address) -> f64 {
         self.escrow_balances.get(address).copied().unwrap_or(0.0)
     }
 }
@@ -353,13 +655,32 @@ impl MarketplaceContract {
 mod tests {
     use super::*;
 
+    const ADMIN: &str = "fdf795f176e75bca";
+    const ALICE: &str = "48175356aa0b5e87";
+    const BOB: &str = "ea6b9a9e64a15d55";
+    const CHARLIE: &str = "570ea4a8b5ec34a1";
+    const CREATOR: &str = "c7e4a09b1d5f3826";
+
+    fn addr(address: &str) -> Address {
+        Address::parse(address).unwrap()
+    }
+
+    fn gem(gem_id: &str) -> GemStatus {
+        GemStatus {
+            gem_id: gem_id.to_string(),
+            soulbound: false,
+            cracked: false,
+            royalty: Some(Royalty { recipient: addr(CREATOR), bps: 500 }),
+        }
+    }
+
     #[test]
     fn test_create_listing() {
-        let mut marketplace = MarketplaceContract::new("admin".to_string(), 2.5, 5.0);
+        let mut marketplace = MarketplaceContract::new(addr(ADMIN), 2.5);
 
         let listing_id = marketplace.create_listing(
-            "GEM-1".to_string(),
-            "alice".to_string(),
+            gem("GEM-1"),
+            addr(ALICE),
             ListingType::FixedPrice,
             100.0,
             None,
@@ -372,11 +693,11 @@ mod tests {
 
     #[test]
     fn test_buy_gem() {
-        let mut marketplace = MarketplaceContract::new("admin".to_string(), 2.5, 5.0);
+        let mut marketplace = MarketplaceContract::new(addr(ADMIN), 2.5);
 
         let listing_id = marketplace.create_listing(
-            "GEM-1".to_string(),
-            "alice".to_string(),
+            gem("GEM-1"),
+            addr(ALICE),
             ListingType::FixedPrice,
             100.0,
             None,
@@ -385,59 +706,254 @@ mod tests {
 
         let sale_id = marketplace.buy(
             &listing_id,
-            "bob".to_string(),
+            addr(BOB),
             100.0,
             1234567891,
-            "creator".to_string(),
         ).unwrap();
 
         assert_eq!(sale_id, "SALE-0");
         assert_eq!(marketplace.active_listings.len(), 0);
 
         // Check balances: seller gets 92.5, creator gets 5, admin gets 2.5
-        assert_eq!(marketplace.get_balance("alice"), 92.5);
-        assert_eq!(marketplace.get_balance("creator"), 5.0);
-        assert_eq!(marketplace.get_balance("admin"), 2.5);
+        assert_eq!(marketplace.get_balance(&addr(ALICE)), 92.5);
+        assert_eq!(marketplace.get_balance(&addr(CREATOR)), 5.0);
+        assert_eq!(marketplace.get_balance(&addr(ADMIN)), 2.5);
+        // The buyer has no say in who receives the royalty
+        assert_eq!(marketplace.get_balance(&addr(BOB)), 0.0);
+        assert_eq!(marketplace.get_sales_history()[0].royalty_paid, 5.0);
+    }
+
+    #[test]
+    fn test_royalty_is_fixed_at_listing() {
+        let mut marketplace = MarketplaceContract::new(addr(ADMIN), 2.5);
+
+        let greedy = GemStatus {
+            royalty: Some(Royalty { recipient: addr(CREATOR), bps: MAX_ROYALTY_BPS + 1 }),
+            ..gem("GEM-1")
+        };
+        assert_eq!(
+            marketplace.create_listing(greedy, addr(ALICE), ListingType::FixedPrice, 100.0, None, 0),
+            Err(MarketError::InvalidRoyalty)
+        );
+
+        let free = GemStatus { royalty: None, ..gem("GEM-2") };
+        let listing_id = marketplace
+            .create_listing(free, addr(ALICE), ListingType::FixedPrice, 100.0, None, 0)
+            .unwrap();
+        marketplace.buy(&listing_id, addr(BOB), 100.0, 1).unwrap();
+        assert_eq!(marketplace.get_balance(&addr(ALICE)), 97.5);
+        assert_eq!(marketplace.get_balance(&addr(BOB)), 0.0);
     }
 
     #[test]
     fn test_auction_bidding() {
-        let mut marketplace = MarketplaceContract::new("admin".to_string(), 2.5, 5.0);
+        let mut marketplace = MarketplaceContract::new(addr(ADMIN), 2.5);
 
         let listing_id = marketplace.create_listing(
-            "GEM-1".to_string(),
-            "alice".to_string(),
+            gem("GEM-1"),
+            addr(ALICE),
             ListingType::Auction,
             100.0,
             Some(86400), // 1 day
             1234567890,
         ).unwrap();
 
-        marketplace.place_bid(&listing_id, "bob".to_string(), 110.0, 1234567900).unwrap();
-        marketplace.place_bid(&listing_id, "charlie".to_string(), 120.0, 1234567910).unwrap();
+        marketplace.place_bid(&listing_id, addr(BOB), 110.0, 1234567900).unwrap();
+        marketplace.place_bid(&listing_id, addr(CHARLIE), 120.0, 1234567910).unwrap();
 
         let listing = marketplace.get_listing(&listing_id).unwrap();
         assert_eq!(listing.highest_bid, Some(120.0));
-        assert_eq!(listing.highest_bidder, Some("charlie".to_string()));
+        assert_eq!(listing.highest_bidder, Some(addr(CHARLIE)));
+
+        // Royalty is paid on the winning bid
+        let sale_id = marketplace.end_auction(&listing_id, 1234567890 + 86400).unwrap();
+        assert_eq!(sale_id, Some("SALE-0".to_string()));
+        assert_eq!(marketplace.get_balance(&addr(CREATOR)), 6.0);
+        assert_eq!(marketplace.get_balance(&addr(ALICE)), 111.0);
     }
 
     #[test]
     fn test_cancel_listing() {
-        let mut marketplace = MarketplaceContract::new("admin".to_string(), 2.5, 5.0);
+        let mut marketplace = MarketplaceContract::new(addr(ADMIN), 2.5);
 
         let listing_id = marketplace.create_listing(
-            "GEM-1".to_string(),
-            "alice".to_string(),
+            gem("GEM-1"),
+            addr(ALICE),
             ListingType::FixedPrice,
             100.0,
             None,
             1234567890,
         ).unwrap();
 
-        marketplace.cancel_listing(&listing_id, "alice").unwrap();
+        marketplace.cancel_listing(&listing_id, &addr(ALICE)).unwrap();
 
         let listing = marketplace.get_listing(&listing_id).unwrap();
         assert_eq!(listing.status, ListingStatus::Cancelled);
         assert_eq!(marketplace.active_listings.len(), 0);
     }
+
+    #[test]
+    fn test_pause() {
+        let mut marketplace = MarketplaceContract::new(addr(ADMIN), 2.5);
+
+        let listing_id = marketplace.create_listing(
+            gem("GEM-1"),
+            addr(ALICE),
+            ListingType::FixedPrice,
+            100.0,
+            None,
+            1234567890,
+        ).unwrap();
+
+        assert!(marketplace.set_paused(true, &addr(ALICE)).is_err());
+        marketplace.set_paused(true, &addr(ADMIN)).unwrap();
+
+        let paused = Err(MarketError::Paused);
+        assert_eq!(
+            marketplace.buy(&listing_id, addr(BOB), 100.0, 1234567891),
+            paused
+        );
+        assert_eq!(
+            marketplace.create_listing(
+                gem("GEM-2"),
+                addr(ALICE),
+                ListingType::Auction,
+                50.0,
+                Some(86400),
+                1234567890,
+            ),
+            paused
+        );
+
+        // Sellers can still pull listings and withdraw while paused
+        marketplace.escrow_balances.insert(addr(ALICE), 10.0);
+        assert_eq!(marketplace.withdraw(&addr(ALICE)), Ok(10.0));
+        marketplace.cancel_listing(&listing_id, &addr(ALICE)).unwrap();
+        assert_eq!(marketplace.get_active_listings().len(), 0);
+
+        marketplace.set_paused(false, &addr(ADMIN)).unwrap();
+        assert!(marketplace.create_listing(
+            gem("GEM-2"),
+            addr(ALICE),
+            ListingType::FixedPrice,
+            50.0,
+            None,
+            1234567890,
+        ).is_ok());
+    }
+
+    #[test]
+    fn test_error_codes() {
+        let mut marketplace = MarketplaceContract::new(addr(ADMIN), 2.5);
+
+        let err = marketplace.buy("LISTING-9", addr(BOB), 10.0, 0).unwrap_err();
+        assert_eq!(err, MarketError::ListingNotFound);
+        assert_eq!(err.code(), 2001);
+        assert_eq!(
+            serde_json::to_value(&err).unwrap(),
+            serde_json::json!({ "code": 2001, "message": "Listing not found" })
+        );
+
+        let listing_id = marketplace.create_listing(
+            gem("GEM-1"),
+            addr(ALICE),
+            ListingType::FixedPrice,
+            100.0,
+            None,
+            1234567890,
+        ).unwrap();
+
+        assert_eq!(
+            marketplace.place_bid(&listing_id, addr(BOB), 150.0, 1234567891),
+            Err(MarketError::NotAuction)
+        );
+        assert_eq!(marketplace.withdraw(&addr(BOB)), Err(MarketError::NoBalance));
+    }
+
+    #[test]
+    fn test_address_validation() {
+        assert_eq!(ALICE.parse::<Address>(), Ok(addr(ALICE)));
+
+        let err = Address::parse("alice ").unwrap_err();
+        assert_eq!(err, MarketError::InvalidAddress("alice ".to_string()));
+        assert_eq!(err.code(), 2015);
+        assert!(Address::parse("48175356AA0B5E87").is_err());
+
+        // Stored state with a malformed address is rejected on load
+        let mut marketplace = MarketplaceContract::new(addr(ADMIN), 2.5);
+        marketplace.escrow_balances.insert(addr(ALICE), 1.0);
+        let state = serde_json::to_string(&marketplace).unwrap();
+        assert!(serde_json::from_str::<MarketplaceContract>(&state).is_ok());
+        let corrupted = state.replace(ALICE, "alice");
+        assert!(serde_json::from_str::<MarketplaceContract>(&corrupted).is_err());
+    }
+
+    #[test]
+    fn test_legacy_state_migration() {
+        // State as the baseline contract wrote it, with free-form account strings
+        let legacy = r#"{
+            "listings": {
+                "LISTING-0": {
+                    "id": "LISTING-0", "gem_id": "GEM-0", "seller": "alice",
+                    "listing_type": "Auction", "price": 10.0, "status": "Active",
+                    "created_at": 0, "expires_at": 100, "highest_bid": 12.0, "highest_bidder": "bob"
+                }
+            },
+            "sales_history": [{
+                "id": "SALE-0", "listing_id": "LISTING-1", "gem_id": "GEM-1", "seller": "bob",
+                "buyer": "alice", "price": 5.0, "timestamp": 0, "royalty_paid": 0.25
+            }],
+            "active_listings": ["LISTING-0"],
+            "listing_counter": 2,
+            "sale_counter": 1,
+            "contract_owner": "system",
+            "marketplace_fee_percent": 2.5,
+            "royalty_percent": 5.0,
+            "escrow_balances": {"bob": 4.75, "Bob": 1.0, "system": 0.125}
+        }"#;
+
+        assert!(serde_json::from_str::<MarketplaceContract>(legacy).is_err());
+
+        let mut addresses = HashMap::from([
+            ("alice".to_string(), addr(ALICE)),
+            ("bob".to_string(), addr(BOB)),
+            ("system".to_string(), addr(ADMIN)),
+        ]);
+        assert_eq!(
+            MarketplaceContract::from_legacy_state(legacy, &addresses).unwrap_err(),
+            MarketError::InvalidAddress("Bob".to_string())
+        );
+
+        addresses.insert("Bob".to_string(), addr(BOB));
+        let marketplace = MarketplaceContract::from_legacy_state(legacy, &addresses).unwrap();
+        assert_eq!(marketplace.contract_owner, addr(ADMIN));
+        assert_eq!(marketplace.get_balance(&addr(BOB)), 5.75);
+        let listing = marketplace.get_listing("LISTING-0").unwrap();
+        assert_eq!(listing.highest_bidder, Some(addr(BOB)));
+        assert_eq!(listing.royalty, None);
+        assert_eq!(marketplace.get_sales_history()[0].buyer, addr(ALICE));
+    }
+
+    #[test]
+    fn test_soulbound_and_cracked_gems_cannot_be_listed() {
+        let mut marketplace = MarketplaceContract::new(addr(ADMIN), 2.5);
+
+        let badge = GemStatus { soulbound: true, ..gem("GEM-1") };
+        let broken = GemStatus { cracked: true, ..gem("GEM-2") };
+        let list = |marketplace: &mut MarketplaceContract, gem: GemStatus| {
+            marketplace.create_listing(
+                gem,
+                addr(ALICE),
+                ListingType::FixedPrice,
+                100.0,
+                None,
+                1234567890,
+            )
+        };
+
+        assert_eq!(list(&mut marketplace, badge), Err(MarketError::Soulbound));
+        assert_eq!(list(&mut marketplace, broken), Err(MarketError::GemCracked));
+        assert_eq!(marketplace.get_active_listings().len(), 0);
+        assert_eq!(marketplace.listing_counter, 0);
+    }
 }